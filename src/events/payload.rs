@@ -122,6 +122,7 @@ impl EventSendPayload for () {
 #[cfg(feature = "serialize")]
 mod serde_payload {
     use super::DynamicSerializer;
+    use crate::ipc::context::Context;
     use crate::payload::EventReceivePayload;
     use crate::prelude::{EventSendPayload, IPCResult};
     use byteorder::ReadBytesExt;
@@ -133,12 +134,36 @@ mod serde_payload {
     pub struct SerdePayload<T> {
         data: T,
         serializer: DynamicSerializer,
+        /// Set by [`negotiated`](Self::negotiated) - when true, the inline
+        /// format byte is left out of `to_payload_bytes` because the peer
+        /// already knows the format from [`negotiate_serializer`].
+        omit_format_byte: bool,
     }
 
     impl<T> SerdePayload<T> {
-        /// Creates a new serde payload with a specified serializer
+        /// Creates a new serde payload with a specified serializer, writing
+        /// an inline format byte so the receiving side can pick the right
+        /// serializer without any prior negotiation.
         pub fn new(serializer: DynamicSerializer, data: T) -> Self {
-            Self { serializer, data }
+            Self {
+                serializer,
+                data,
+                omit_format_byte: false,
+            }
+        }
+
+        /// Creates a new serde payload that omits the inline format byte,
+        /// for use once a serializer has been agreed on for the whole
+        /// connection via [`negotiate_serializer`]. The receiving side must
+        /// read it back with [`from_payload_bytes_negotiated`](Self::from_payload_bytes_negotiated)
+        /// rather than the `EventReceivePayload` impl, which always expects
+        /// the inline byte.
+        pub fn negotiated(serializer: DynamicSerializer, data: T) -> Self {
+            Self {
+                serializer,
+                data,
+                omit_format_byte: true,
+            }
         }
 
         pub fn data(self) -> T {
@@ -154,6 +179,7 @@ mod serde_payload {
             Self {
                 serializer: self.serializer.clone(),
                 data: self.data.clone(),
+                omit_format_byte: self.omit_format_byte,
             }
         }
     }
@@ -163,9 +189,15 @@ mod serde_payload {
         T: Serialize,
     {
         fn to_payload_bytes(self) -> IPCResult<Vec<u8>> {
-            let mut buf = Vec::new();
-            let mut data_bytes = self.serializer.serialize(self.data)?;
+            let omit_format_byte = self.omit_format_byte;
             let format_id = self.serializer as u8;
+            let mut data_bytes = self.serializer.serialize(self.data)?;
+
+            if omit_format_byte {
+                return Ok(data_bytes);
+            }
+
+            let mut buf = Vec::with_capacity(1 + data_bytes.len());
             buf.push(format_id);
             buf.append(&mut data_bytes);
 
@@ -182,7 +214,171 @@ mod serde_payload {
             let serializer = DynamicSerializer::from_primitive(format_id as usize)?;
             let data = serializer.deserialize(reader)?;
 
-            Ok(Self { serializer, data })
+            Ok(Self::new(serializer, data))
+        }
+    }
+
+    impl<T> SerdePayload<T> {
+        /// Decodes a payload that was written with
+        /// [`negotiated`](Self::negotiated), using the serializer agreed on
+        /// during negotiation rather than an inline format byte.
+        pub fn from_payload_bytes_negotiated<R: Read>(
+            reader: R,
+            serializer: DynamicSerializer,
+        ) -> IPCResult<Self>
+        where
+            T: DeserializeOwned,
+        {
+            let data = serializer.deserialize(reader)?;
+
+            Ok(Self::negotiated(serializer, data))
+        }
+    }
+
+    /// Context key exposing the [`DynamicSerializer`] that was agreed on
+    /// during connection setup via [`negotiate_serializer`].
+    pub struct NegotiatedSerializerKey;
+
+    impl typemap_rev::TypeMapKey for NegotiatedSerializerKey {
+        type Value = DynamicSerializer;
+    }
+
+    /// Runs a one-time serializer negotiation right after the transport
+    /// connects: both peers send the protocol `FORMAT_VERSION` and the
+    /// `DynamicSerializer` variants they support, then independently pick
+    /// the highest one they have in common, instead of leaving every later
+    /// payload to fail one at a time. Fails with `UnsupportedVersion` if the
+    /// peer speaks a different protocol version, or a plain I/O error if the
+    /// two peers' supported formats don't overlap at all.
+    pub async fn negotiate_serializer<S>(
+        stream: &mut S,
+        supported: &[DynamicSerializer],
+    ) -> IPCResult<DynamicSerializer>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut outgoing = Vec::with_capacity(2 + supported.len());
+        outgoing.push(crate::protocol::FORMAT_VERSION);
+        outgoing.push(supported.len() as u8);
+        outgoing.extend(supported.iter().map(|serializer| *serializer as u8));
+        stream.write_all(&outgoing).await?;
+        stream.flush().await?;
+
+        let their_version = stream.read_u8().await?;
+        if their_version != crate::protocol::FORMAT_VERSION {
+            return Err(crate::error::Error::UnsupportedVersion(their_version));
+        }
+
+        let their_count = stream.read_u8().await? as usize;
+        let mut their_formats = Vec::with_capacity(their_count);
+        for _ in 0..their_count {
+            their_formats.push(stream.read_u8().await? as usize);
+        }
+
+        supported
+            .iter()
+            .filter(|serializer| their_formats.contains(&(**serializer as usize)))
+            .max_by_key(|serializer| **serializer as usize)
+            .cloned()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "no serializer format is supported by both peers",
+                )
+                .into()
+            })
+    }
+
+    /// Runs [`negotiate_serializer`] and, on success, inserts the agreed
+    /// [`DynamicSerializer`] into `ctx` under [`NegotiatedSerializerKey`] so
+    /// later calls like [`SerdePayload::negotiated`]/[`SerdePayload::from_payload_bytes_negotiated`]
+    /// can look it up instead of every payload carrying its own inline format
+    /// byte. This is the glue `protocol_connect`/`protocol_accept` are
+    /// expected to call once, right after connecting - `negotiate_serializer`
+    /// alone only returns the agreed format without exposing it.
+    ///
+    /// Nothing in this crate currently calls this automatically: the
+    /// connection bootstrap that would run it once per connection isn't part
+    /// of this snapshot, so callers need to invoke it themselves right after
+    /// `protocol_connect`/`protocol_accept` until that wiring exists.
+    pub async fn negotiate_and_store<S>(
+        stream: &mut S,
+        ctx: &Context,
+        supported: &[DynamicSerializer],
+    ) -> IPCResult<DynamicSerializer>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let negotiated = negotiate_serializer(stream, supported).await?;
+        ctx.data
+            .write()
+            .await
+            .insert::<NegotiatedSerializerKey>(negotiated);
+
+        Ok(negotiated)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+        #[tokio::test]
+        async fn negotiate_serializer_agrees_on_common_format() {
+            let format = DynamicSerializer::from_primitive(0).expect("format 0 is supported");
+            let (mut a, mut b) = duplex(1024);
+
+            let a_fut = negotiate_serializer(&mut a, &[format]);
+            let b_fut = negotiate_serializer(&mut b, &[format]);
+            let (a_result, b_result) = tokio::join!(a_fut, b_fut);
+
+            assert_eq!(a_result.unwrap() as u8, format as u8);
+            assert_eq!(b_result.unwrap() as u8, format as u8);
+        }
+
+        #[tokio::test]
+        async fn negotiate_serializer_fails_on_version_mismatch() {
+            let format = DynamicSerializer::from_primitive(0).expect("format 0 is supported");
+            let (mut a, mut b) = duplex(1024);
+
+            let a_fut = negotiate_serializer(&mut a, &[format]);
+            let b_fut = async {
+                b.write_all(&[
+                    crate::protocol::FORMAT_VERSION.wrapping_add(1),
+                    1,
+                    format as u8,
+                ])
+                .await
+                .unwrap();
+                b.flush().await.unwrap();
+            };
+
+            let (a_result, _) = tokio::join!(a_fut, b_fut);
+            assert!(matches!(
+                a_result,
+                Err(crate::error::Error::UnsupportedVersion(_))
+            ));
+        }
+
+        #[tokio::test]
+        async fn negotiate_serializer_fails_when_formats_dont_overlap() {
+            let format = DynamicSerializer::from_primitive(0).expect("format 0 is supported");
+            let (mut a, mut b) = duplex(1024);
+
+            let a_fut = negotiate_serializer(&mut a, &[format]);
+            // A peer on the same protocol version claiming an unrecognized
+            // format id, so the overlap check can't match anything.
+            let b_fut = async {
+                b.write_all(&[crate::protocol::FORMAT_VERSION, 1, 0xFF])
+                    .await
+                    .unwrap();
+                b.flush().await.unwrap();
+            };
+
+            let (a_result, _) = tokio::join!(a_fut, b_fut);
+            assert!(a_result.is_err());
         }
     }
 }
@@ -0,0 +1,132 @@
+//! Streaming payloads for large transfers.
+//!
+//! [`EventSendPayload`]/[`EventReceivePayload`] fully buffer their body in
+//! memory, which is fine for typical event payloads but wasteful for large
+//! blobs (files, images, ...) sent over the transport. [`StreamPayload`]
+//! instead writes a `u64` total-length header followed by the body read
+//! from an [`AsyncRead`] in fixed-size chunks, and hands the receiving side
+//! an [`AsyncRead`] bounded to exactly that many bytes rather than a
+//! materialized buffer. [`StreamTandemPayload`] composes it with a small
+//! serde header, the same way [`TandemPayload`](super::payload::TandemPayload)
+//! composes two buffered payloads.
+use crate::payload::{EventReceivePayload, EventSendPayload};
+use crate::prelude::IPCResult;
+use std::cmp::min;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, Take};
+
+/// Chunk size used when streaming a payload body, matching the batching
+/// bound the encrypted writer coalesces small writes into.
+pub const STREAM_CHUNK_SIZE: usize = 1024;
+
+/// Largest header a [`StreamTandemPayload`] is allowed to claim. The header
+/// is meant to be small (unlike the streamed body, which has no size limit
+/// by design) - without a cap, a peer-controlled `header_len` could force an
+/// allocation of up to `u64::MAX` bytes before a single header byte arrives.
+pub const MAX_HEADER_LEN: u64 = 1024 * 1024;
+
+/// A payload whose body is streamed from an [`AsyncRead`] instead of being
+/// materialized into a `Vec<u8>` up front.
+pub struct StreamPayload<R> {
+    total_len: u64,
+    reader: R,
+}
+
+impl<R> StreamPayload<R> {
+    /// Creates a new streamed payload of `total_len` bytes, read from
+    /// `reader`. The caller is responsible for `total_len` being accurate -
+    /// the receiving side trusts it to bound how many bytes belong to this
+    /// payload.
+    pub fn new(total_len: u64, reader: R) -> Self {
+        Self { total_len, reader }
+    }
+}
+
+impl<R> StreamPayload<R>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    /// Writes the `u64` length header followed by the body, read from the
+    /// inner `AsyncRead` in chunks of at most `u32::MAX` bytes and at most
+    /// [`STREAM_CHUNK_SIZE`] bytes at a time.
+    pub async fn write_to<W: AsyncWrite + Unpin + Send>(mut self, writer: &mut W) -> IPCResult<()> {
+        writer.write_u64(self.total_len).await?;
+
+        let mut remaining = self.total_len;
+        let mut buf = vec![0u8; min(STREAM_CHUNK_SIZE as u64, u32::MAX as u64) as usize];
+        while remaining > 0 {
+            let chunk_len = min(buf.len() as u64, remaining) as usize;
+            self.reader.read_exact(&mut buf[..chunk_len]).await?;
+            writer.write_all(&buf[..chunk_len]).await?;
+            remaining -= chunk_len as u64;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the `u64` length header written by [`StreamPayload::write_to`] and
+/// returns the body as an [`AsyncRead`] bounded to exactly that many bytes,
+/// so the handler can stream it onward (e.g. into a file) with bounded
+/// memory instead of receiving a materialized buffer.
+pub async fn read_stream_payload<R>(mut reader: R) -> IPCResult<Take<R>>
+where
+    R: AsyncRead + Unpin,
+{
+    let total_len = reader.read_u64().await?;
+
+    Ok(reader.take(total_len))
+}
+
+/// A small serde-encoded header travelling alongside a large streamed body,
+/// the streaming counterpart to [`TandemPayload`](super::payload::TandemPayload).
+pub struct StreamTandemPayload<P, R> {
+    header: P,
+    body: StreamPayload<R>,
+}
+
+impl<P, R> StreamTandemPayload<P, R> {
+    pub fn new(header: P, body: StreamPayload<R>) -> Self {
+        Self { header, body }
+    }
+}
+
+impl<P, R> StreamTandemPayload<P, R>
+where
+    P: EventSendPayload,
+    R: AsyncRead + Unpin + Send,
+{
+    /// Writes the header (length-prefixed, fully buffered) followed by the
+    /// streamed body.
+    pub async fn write_to<W: AsyncWrite + Unpin + Send>(self, writer: &mut W) -> IPCResult<()> {
+        let header_bytes = self.header.to_payload_bytes()?;
+        writer.write_u64(header_bytes.len() as u64).await?;
+        writer.write_all(&header_bytes).await?;
+
+        self.body.write_to(writer).await
+    }
+}
+
+/// Reads a header written by [`StreamTandemPayload::write_to`] and returns
+/// it together with the remaining body as a bounded [`AsyncRead`].
+pub async fn read_stream_tandem_payload<P, R>(mut reader: R) -> IPCResult<(P, Take<R>)>
+where
+    P: EventReceivePayload,
+    R: AsyncRead + Unpin,
+{
+    let header_len = reader.read_u64().await?;
+    if header_len > MAX_HEADER_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "stream tandem payload header exceeds the maximum allowed length",
+        )
+        .into());
+    }
+    let mut header_bytes = vec![0u8; header_len as usize];
+    reader.read_exact(&mut header_bytes).await?;
+    let header = P::from_payload_bytes(header_bytes.as_slice())?;
+
+    let body = read_stream_payload(reader).await?;
+
+    Ok((header, body))
+}
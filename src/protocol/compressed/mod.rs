@@ -0,0 +1,122 @@
+//! A zstd transform wrapper over any [`AsyncProtocolStream`], structured like
+//! [`EncryptedStream`](crate::protocol::encrypted::EncryptedStream): a
+//! [`CompressedReadStream`]/[`CompressedWriteStream`] pair buffering each
+//! block through `zstd::bulk` instead of a cipher.
+//!
+//! Compose it *over* the encryption wrapper (as the outer type), not under it -
+//! `CompressedStream<EncryptedStream<TcpStream>>` compresses plaintext and
+//! then encrypts the result; encrypted bytes are already incompressible, so
+//! the other order buys nothing.
+mod io_impl;
+mod package;
+
+pub use package::CompressedPackage;
+
+use crate::prelude::{AsyncProtocolStream, AsyncProtocolStreamSplit, IPCResult};
+use async_trait::async_trait;
+use bytes::BytesMut;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+
+/// `StreamOptions` for [`CompressedStream`]: the wrapped transport's own
+/// options plus the zstd compression level to use for writes.
+pub struct CompressionOptions<O> {
+    pub inner: O,
+    pub level: i32,
+}
+
+impl<O: Default> Default for CompressionOptions<O> {
+    fn default() -> Self {
+        Self {
+            inner: O::default(),
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+type ReadFuture<T> = Pin<Box<dyn Future<Output = (io::Result<BytesMut>, T)> + Send>>;
+type WriteFuture<T> = Pin<Box<dyn Future<Output = (io::Result<()>, T)> + Send>>;
+type ShutdownFuture = Pin<Box<dyn Future<Output = io::Result<()>> + Send>>;
+
+pub struct CompressedReadStream<T> {
+    inner: Option<T>,
+    remaining: BytesMut,
+    fut: Option<ReadFuture<T>>,
+}
+
+impl<T> CompressedReadStream<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner: Some(inner),
+            remaining: BytesMut::new(),
+            fut: None,
+        }
+    }
+}
+
+pub struct CompressedWriteStream<T> {
+    inner: Option<T>,
+    level: i32,
+    buffer: BytesMut,
+    fut_write: Option<WriteFuture<T>>,
+    fut_flush: Option<WriteFuture<T>>,
+    fut_shutdown: Option<ShutdownFuture>,
+}
+
+impl<T> CompressedWriteStream<T> {
+    fn new(inner: T, level: i32) -> Self {
+        Self {
+            inner: Some(inner),
+            level,
+            buffer: BytesMut::new(),
+            fut_write: None,
+            fut_flush: None,
+            fut_shutdown: None,
+        }
+    }
+}
+
+/// A stream that transparently zstd-compresses everything written to it and
+/// inflates everything read from it, layered over any [`AsyncProtocolStream`].
+pub struct CompressedStream<T: AsyncProtocolStreamSplit> {
+    read_half: CompressedReadStream<T::OwnedSplitReadHalf>,
+    write_half: CompressedWriteStream<T::OwnedSplitWriteHalf>,
+}
+
+impl<T: AsyncProtocolStreamSplit> CompressedStream<T> {
+    pub fn new(read_half: T::OwnedSplitReadHalf, write_half: T::OwnedSplitWriteHalf, level: i32) -> Self {
+        Self {
+            read_half: CompressedReadStream::new(read_half),
+            write_half: CompressedWriteStream::new(write_half, level),
+        }
+    }
+}
+
+impl<T: AsyncProtocolStreamSplit> AsyncProtocolStreamSplit for CompressedStream<T> {
+    type OwnedSplitReadHalf = CompressedReadStream<T::OwnedSplitReadHalf>;
+    type OwnedSplitWriteHalf = CompressedWriteStream<T::OwnedSplitWriteHalf>;
+
+    fn protocol_into_split(self) -> (Self::OwnedSplitReadHalf, Self::OwnedSplitWriteHalf) {
+        (self.read_half, self.write_half)
+    }
+}
+
+#[async_trait]
+impl<T> AsyncProtocolStream for CompressedStream<T>
+where
+    T: AsyncProtocolStream + AsyncProtocolStreamSplit,
+{
+    type AddressType = T::AddressType;
+    type StreamOptions = CompressionOptions<T::StreamOptions>;
+
+    async fn protocol_connect(
+        address: Self::AddressType,
+        options: Self::StreamOptions,
+    ) -> IPCResult<Self> {
+        let inner = T::protocol_connect(address, options.inner).await?;
+        let (read_half, write_half) = inner.protocol_into_split();
+
+        Ok(Self::new(read_half, write_half, options.level))
+    }
+}
@@ -0,0 +1,60 @@
+use crate::prelude::IPCResult;
+use bytes::Bytes;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Largest `compressed_len`/`uncompressed_len` accepted from a peer. Both
+/// fields are read off the wire before anything has been authenticated, so
+/// without a cap a single small frame could claim a ~4 GB length and force
+/// an allocation (or, for `uncompressed_len`, a decompression) of that size.
+pub const MAX_FRAME_LEN: u32 = 64 * 1024 * 1024;
+
+/// A single framed, zstd-compressed block: a compressed length, the
+/// original uncompressed length (so the reader can size its decompression
+/// buffer up front instead of growing it), and the compressed bytes.
+pub struct CompressedPackage {
+    uncompressed_len: u32,
+    bytes: Bytes,
+}
+
+impl CompressedPackage {
+    pub fn new(uncompressed_len: u32, bytes: Bytes) -> Self {
+        Self {
+            uncompressed_len,
+            bytes,
+        }
+    }
+
+    /// Returns the uncompressed length and the compressed bytes.
+    pub fn into_parts(self) -> (u32, Bytes) {
+        (self.uncompressed_len, self.bytes)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(8 + self.bytes.len());
+        buf.extend_from_slice(&(self.bytes.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&self.uncompressed_len.to_be_bytes());
+        buf.extend_from_slice(&self.bytes);
+
+        buf
+    }
+
+    pub async fn from_async_read<R: AsyncRead + Unpin>(reader: &mut R) -> IPCResult<Self> {
+        let compressed_len = reader.read_u32().await?;
+        let uncompressed_len = reader.read_u32().await?;
+        if compressed_len > MAX_FRAME_LEN || uncompressed_len > MAX_FRAME_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "compressed frame exceeds the maximum allowed length",
+            )
+            .into());
+        }
+        let mut bytes = vec![0u8; compressed_len as usize];
+        reader.read_exact(&mut bytes).await?;
+
+        Ok(Self {
+            uncompressed_len,
+            bytes: Bytes::from(bytes),
+        })
+    }
+}
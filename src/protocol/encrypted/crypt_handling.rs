@@ -0,0 +1,270 @@
+//! Symmetric encryption for the [`EncryptedStream`](super::EncryptedStream)
+//! transport: a ChaCha20Poly1305 [`CipherBox`] per direction, ratcheting its
+//! nonce off a monotonic counter and rekeying itself in-band before that
+//! counter (or the key it protects) is worn out.
+use bytes::Bytes;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand_core::{OsRng, RngCore};
+use std::io;
+
+pub const DEFAULT_REKEY_THRESHOLD: u64 = 1 << 32;
+
+/// Which half of the connection a [`CipherBox`] protects. Mixed into the
+/// nonce so the two directions never derive the same nonce from the same
+/// counter value, even if both were ever keyed identically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Initiator,
+    Responder,
+}
+
+impl Direction {
+    fn tag(self) -> [u8; 4] {
+        match self {
+            Direction::Initiator => [0, 0, 0, 0],
+            Direction::Responder => [0, 0, 0, 1],
+        }
+    }
+
+    /// The opposite role, i.e. the peer's own role, used when checking a signed
+    /// handshake transcript: whichever hash the peer signed was tagged
+    /// with *their* role, not ours.
+    pub fn other(self) -> Self {
+        match self {
+            Direction::Initiator => Direction::Responder,
+            Direction::Responder => Direction::Initiator,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FrameKind {
+    Data = 0,
+    Rekey = 1,
+}
+
+impl FrameKind {
+    fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0 => Ok(FrameKind::Data),
+            1 => Ok(FrameKind::Rekey),
+            _ => Err(decrypt_error("received unknown encrypted frame kind")),
+        }
+    }
+}
+
+/// The result of decrypting one wire frame: either application data, or a
+/// rekey frame that [`CipherBox::decrypt`] has already applied internally,
+/// transparent to the caller aside from this marker.
+pub enum DecryptedFrame {
+    Data(Bytes),
+    RekeyApplied,
+}
+
+/// A ChaCha20Poly1305 cipher for one direction of an [`EncryptedStream`](super::EncryptedStream),
+/// with a nonce ratcheting off a monotonic counter.
+///
+/// Once `rekey_threshold` messages have been encrypted, the next call to
+/// [`encrypt`](Self::encrypt) transparently prepends a `Rekey` frame
+/// carrying a fresh, randomly generated key (encrypted under the old one)
+/// before the data frame, and switches to it. [`decrypt`](Self::decrypt)
+/// applies an incoming `Rekey` frame the same way and reports
+/// [`DecryptedFrame::RekeyApplied`] so the caller can skip straight to the
+/// next frame. Because the new key is independent random data rather than
+/// something derived from the old key (or any other long-lived secret), a
+/// later compromise of one key cannot be used to recompute the keys that
+/// came before it.
+pub struct CipherBox {
+    cipher: ChaCha20Poly1305,
+    chain_key: [u8; 32],
+    direction: Direction,
+    counter: u64,
+    rekey_threshold: u64,
+}
+
+impl CipherBox {
+    /// Creates a new cipher box from the chain key produced by the initial
+    /// handshake, using the default rekey threshold.
+    pub fn new(chain_key: [u8; 32], direction: Direction) -> Self {
+        Self::with_threshold(chain_key, direction, DEFAULT_REKEY_THRESHOLD)
+    }
+
+    /// Creates a new cipher box with a custom rekey threshold, mainly
+    /// useful for tests that want to exercise the rekey path quickly.
+    pub fn with_threshold(chain_key: [u8; 32], direction: Direction, rekey_threshold: u64) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&chain_key)),
+            chain_key,
+            direction,
+            counter: 0,
+            rekey_threshold,
+        }
+    }
+
+    /// Whether this cipher's counter has crossed `rekey_threshold`, i.e. the
+    /// next [`encrypt`](Self::encrypt) call will inject a rekey frame.
+    pub fn needs_rekey(&self) -> bool {
+        self.counter >= self.rekey_threshold
+    }
+
+    fn nonce(&self) -> Nonce {
+        let mut bytes = [0u8; 12];
+        bytes[..4].copy_from_slice(&self.direction.tag());
+        bytes[4..].copy_from_slice(&self.counter.to_be_bytes());
+
+        *Nonce::from_slice(&bytes)
+    }
+
+    fn set_key(&mut self, next_key: [u8; 32]) {
+        self.chain_key = next_key;
+        self.cipher = ChaCha20Poly1305::new(Key::from_slice(&next_key));
+        self.counter = 0;
+    }
+
+    fn encrypt_frame(&mut self, kind: FrameKind, payload: &[u8]) -> io::Result<Bytes> {
+        let nonce = self.nonce();
+        let mut plaintext = Vec::with_capacity(1 + payload.len());
+        plaintext.push(kind as u8);
+        plaintext.extend_from_slice(payload);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| encrypt_error("failed to encrypt frame"))?;
+        self.counter += 1;
+
+        Ok(Bytes::from(ciphertext))
+    }
+
+    /// Encrypts one plaintext block, returning the frames that must reach
+    /// the peer, in order. Normally this is a single data frame; once
+    /// `rekey_threshold` is crossed it is a leading rekey frame (switching
+    /// this box to a fresh key) followed by the data frame encrypted under
+    /// the new key.
+    pub fn encrypt(&mut self, plaintext: Bytes) -> io::Result<Vec<Bytes>> {
+        let mut frames = Vec::with_capacity(2);
+
+        if self.needs_rekey() {
+            let mut next_key = [0u8; 32];
+            OsRng.fill_bytes(&mut next_key);
+            frames.push(self.encrypt_frame(FrameKind::Rekey, &next_key)?);
+            self.set_key(next_key);
+        }
+        frames.push(self.encrypt_frame(FrameKind::Data, plaintext.as_ref())?);
+
+        Ok(frames)
+    }
+
+    /// Decrypts one wire frame. A rekey frame is applied in place and
+    /// reported as [`DecryptedFrame::RekeyApplied`] rather than handed to
+    /// the caller as data - callers should loop until they see
+    /// [`DecryptedFrame::Data`]. A decrypt failure (AEAD tag mismatch) is
+    /// always a hard error - it must never be retried with a different key
+    /// or nonce.
+    pub fn decrypt(&mut self, ciphertext: Bytes) -> io::Result<DecryptedFrame> {
+        let nonce = self.nonce();
+        let plaintext = self
+            .cipher
+            .decrypt(&nonce, ciphertext.as_ref())
+            .map_err(|_| decrypt_error("failed to decrypt frame (AEAD tag mismatch)"))?;
+        self.counter += 1;
+
+        let (&kind_byte, payload) = plaintext
+            .split_first()
+            .ok_or_else(|| decrypt_error("received empty encrypted frame"))?;
+        match FrameKind::from_byte(kind_byte)? {
+            FrameKind::Data => Ok(DecryptedFrame::Data(Bytes::copy_from_slice(payload))),
+            FrameKind::Rekey => {
+                if payload.len() != 32 {
+                    return Err(decrypt_error("malformed rekey frame"));
+                }
+                let mut next_key = [0u8; 32];
+                next_key.copy_from_slice(payload);
+                self.set_key(next_key);
+
+                Ok(DecryptedFrame::RekeyApplied)
+            }
+        }
+    }
+}
+
+fn encrypt_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
+
+fn decrypt_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(threshold: u64) -> (CipherBox, CipherBox) {
+        let chain_key = [7u8; 32];
+        let write = CipherBox::with_threshold(chain_key, Direction::Initiator, threshold);
+        let read = CipherBox::with_threshold(chain_key, Direction::Initiator, threshold);
+
+        (write, read)
+    }
+
+    #[test]
+    fn encrypt_decrypt_roundtrip() {
+        let (mut write, mut read) = pair(DEFAULT_REKEY_THRESHOLD);
+
+        for i in 0..8u8 {
+            let plaintext = Bytes::from(vec![i; 16]);
+            let frames = write.encrypt(plaintext.clone()).unwrap();
+            assert_eq!(frames.len(), 1, "no rekey expected below the threshold");
+
+            match read.decrypt(frames[0].clone()).unwrap() {
+                DecryptedFrame::Data(bytes) => assert_eq!(bytes, plaintext),
+                DecryptedFrame::RekeyApplied => panic!("unexpected rekey"),
+            }
+        }
+    }
+
+    #[test]
+    fn rekey_is_applied_transparently() {
+        let (mut write, mut read) = pair(2);
+
+        // First two messages stay under the threshold.
+        for _ in 0..2 {
+            let frames = write.encrypt(Bytes::from_static(b"before")).unwrap();
+            assert_eq!(frames.len(), 1);
+            assert!(matches!(
+                read.decrypt(frames[0].clone()).unwrap(),
+                DecryptedFrame::Data(_)
+            ));
+        }
+
+        // The third crosses it: a rekey frame now leads the data frame.
+        let frames = write.encrypt(Bytes::from_static(b"after")).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(
+            read.decrypt(frames[0].clone()).unwrap(),
+            DecryptedFrame::RekeyApplied
+        ));
+        match read.decrypt(frames[1].clone()).unwrap() {
+            DecryptedFrame::Data(bytes) => assert_eq!(bytes.as_ref(), b"after"),
+            DecryptedFrame::RekeyApplied => panic!("unexpected second rekey"),
+        }
+
+        // Both sides switched to the same fresh key and reset their counter.
+        assert_eq!(write.chain_key, read.chain_key);
+        assert_ne!(write.chain_key, [7u8; 32]);
+        assert!(!write.needs_rekey());
+    }
+
+    #[test]
+    fn decrypt_rejects_tampered_frame() {
+        let (mut write, mut read) = pair(DEFAULT_REKEY_THRESHOLD);
+        let mut frames = write.encrypt(Bytes::from_static(b"hello")).unwrap();
+        let mut tampered = frames.remove(0).to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xff;
+
+        assert!(read.decrypt(Bytes::from(tampered)).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Mutual peer authentication for the encrypted transport.
+//!
+//! The anonymous x25519 exchange behind [`CipherBox`](super::crypt_handling::CipherBox)
+//! only gives confidentiality - either side could be a MITM. This module adds
+//! an authenticated layer on top of it: each peer holds a long-term Ed25519
+//! identity keypair, signs a hash of the ephemeral handshake transcript, and
+//! sends that signature (plus its public key) as the first frame of the
+//! freshly established encrypted channel.
+//! [`EncryptedStream::protocol_connect`](super::EncryptedStream) runs
+//! [`authenticate`] right after the `CipherBox`es for both directions are in
+//! place and keeps the resulting [`VerifiedIdentity`] on the stream; it does
+//! not touch any [`Context`](crate::ipc::context::Context).
+//! [`authenticate_and_store`] additionally stores the identity on a
+//! `Context` via [`RemoteIdentityKey`] so handlers can authorize events
+//! per-peer, but nothing in this module calls it automatically.
+use crate::ipc::context::Context;
+use crate::prelude::IPCResult;
+use bytes::Bytes;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use sha2::{Digest, Sha256};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use typemap_rev::TypeMapKey;
+use x25519_dalek::PublicKey as X25519PublicKey;
+
+use super::crypt_handling::Direction;
+use crate::prelude::encrypted::EncryptedPackage;
+
+const PUBLIC_KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// A peer's long-term identity keypair, used to sign the handshake
+/// transcript so the ephemeral x25519 exchange can't be silently MITM'd.
+pub struct IdentityKeyPair(Keypair);
+
+impl IdentityKeyPair {
+    pub fn new(keypair: Keypair) -> Self {
+        Self(keypair)
+    }
+
+    /// The public half of this identity, safe to hand to peers and to put
+    /// on an allowlist.
+    pub fn public_key(&self) -> PublicKey {
+        self.0.public
+    }
+}
+
+/// The identity a peer presented during the authenticated handshake, once
+/// its signature over the transcript has been verified (and, if an
+/// allowlist was supplied, the key has been found on it).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifiedIdentity {
+    public_key: PublicKey,
+}
+
+impl VerifiedIdentity {
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+}
+
+/// Context key exposing the remote peer's [`VerifiedIdentity`] once the
+/// authenticated handshake has completed.
+pub struct RemoteIdentityKey;
+
+impl TypeMapKey for RemoteIdentityKey {
+    type Value = VerifiedIdentity;
+}
+
+/// Hashes both ephemeral public keys from the x25519 exchange together with
+/// a role tag, so each side signs (and can only replay) a transcript that is
+/// unique to this connection and this direction.
+pub fn transcript_hash(
+    local_ephemeral_public: &X25519PublicKey,
+    remote_ephemeral_public: &X25519PublicKey,
+    role: Direction,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(local_ephemeral_public.as_bytes());
+    hasher.update(remote_ephemeral_public.as_bytes());
+    hasher.update(&[match role {
+        Direction::Initiator => 0u8,
+        Direction::Responder => 1u8,
+    }]);
+
+    hasher.finalize().into()
+}
+
+/// Performs the mutual authentication handshake over an already-encrypted
+/// stream: signs and sends our identity assertion, then reads and verifies
+/// the peer's. Fails the connection if the signature does not check out or
+/// - when `allowlist` is supplied - if the presented key isn't pinned.
+///
+/// `own_role` is our [`Direction`] in the handshake this transcript came
+/// from. The hash we sign must be tagged with our own role, and the hash we
+/// verify the peer's signature against must be tagged with *their* role -
+/// [`transcript_hash`] produces a different 32 bytes per role by design, so
+/// passing a single hash to both sign and verify would make verification
+/// fail for every honest peer.
+pub async fn authenticate<S>(
+    stream: &mut S,
+    local_ephemeral_public: &X25519PublicKey,
+    remote_ephemeral_public: &X25519PublicKey,
+    own_role: Direction,
+    identity: &IdentityKeyPair,
+    allowlist: Option<&[PublicKey]>,
+) -> IPCResult<VerifiedIdentity>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let own_hash = transcript_hash(local_ephemeral_public, remote_ephemeral_public, own_role);
+    let peer_hash = transcript_hash(remote_ephemeral_public, local_ephemeral_public, own_role.other());
+
+    let signature = identity.0.sign(&own_hash);
+
+    let mut outgoing = Vec::with_capacity(PUBLIC_KEY_LEN + SIGNATURE_LEN);
+    outgoing.extend_from_slice(identity.public_key().as_bytes());
+    outgoing.extend_from_slice(&signature.to_bytes());
+    let package = EncryptedPackage::new(Bytes::from(outgoing)).into_bytes();
+    stream.write_all(&package).await?;
+    stream.flush().await?;
+
+    let incoming = EncryptedPackage::from_async_read(stream).await?;
+    let bytes = incoming.into_inner();
+    if bytes.len() != PUBLIC_KEY_LEN + SIGNATURE_LEN {
+        return Err(auth_error("malformed identity assertion").into());
+    }
+
+    let their_public = PublicKey::from_bytes(&bytes[..PUBLIC_KEY_LEN])
+        .map_err(|_| auth_error("invalid identity public key"))?;
+    let their_signature = Signature::from_bytes(&bytes[PUBLIC_KEY_LEN..])
+        .map_err(|_| auth_error("invalid identity signature"))?;
+
+    their_public
+        .verify(&peer_hash, &their_signature)
+        .map_err(|_| auth_error("identity signature verification failed"))?;
+
+    if let Some(allowlist) = allowlist {
+        let pinned = allowlist
+            .iter()
+            .any(|pinned_key| pinned_key.as_bytes() == their_public.as_bytes());
+        if !pinned {
+            return Err(auth_error("peer identity key is not on the pinned allowlist").into());
+        }
+    }
+
+    Ok(VerifiedIdentity {
+        public_key: their_public,
+    })
+}
+
+fn auth_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message)
+}
+
+/// Runs [`authenticate`] and, on success, inserts the resulting
+/// [`VerifiedIdentity`] into `ctx` under [`RemoteIdentityKey`] so handlers
+/// can look up the remote peer's identity for the lifetime of the
+/// connection. `authenticate` alone only returns the identity without
+/// exposing it. [`EncryptedStream::protocol_connect`](super::EncryptedStream)
+/// does not call this function; it calls `authenticate` and keeps the
+/// identity on the stream.
+pub async fn authenticate_and_store<S>(
+    stream: &mut S,
+    ctx: &Context,
+    local_ephemeral_public: &X25519PublicKey,
+    remote_ephemeral_public: &X25519PublicKey,
+    own_role: Direction,
+    identity: &IdentityKeyPair,
+    allowlist: Option<&[PublicKey]>,
+) -> IPCResult<VerifiedIdentity>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let verified = authenticate(
+        stream,
+        local_ephemeral_public,
+        remote_ephemeral_public,
+        own_role,
+        identity,
+        allowlist,
+    )
+    .await?;
+    ctx.data.write().await.insert::<RemoteIdentityKey>(verified);
+
+    Ok(verified)
+}
+
+/// `StreamOptions` for the encrypted transport: the
+/// wrapped transport's own options (`()` for plain TCP) plus what the
+/// authenticated handshake needs. Used as `EncryptedStream<T>::StreamOptions`
+/// so callers that previously had nothing to configure can now supply an
+/// identity and, optionally, pin the peers they are willing to talk to.
+pub struct EncryptedStreamOptions<O> {
+    pub inner: O,
+    pub identity: IdentityKeyPair,
+    pub allowlist: Option<Vec<PublicKey>>,
+}
+
+impl<O: Default> EncryptedStreamOptions<O> {
+    /// Creates options with no peer pinning: any peer that presents a
+    /// validly signed identity is accepted.
+    pub fn new(identity: IdentityKeyPair) -> Self {
+        Self {
+            inner: O::default(),
+            identity,
+            allowlist: None,
+        }
+    }
+
+    /// Restricts accepted peers to those whose identity public key is in
+    /// `allowlist`.
+    pub fn with_allowlist(mut self, allowlist: Vec<PublicKey>) -> Self {
+        self.allowlist = Some(allowlist);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Keypair;
+    use rand_core::OsRng;
+    use tokio::io::duplex;
+    use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey};
+
+    fn identity() -> IdentityKeyPair {
+        IdentityKeyPair::new(Keypair::generate(&mut OsRng))
+    }
+
+    fn ephemeral_public() -> X25519PublicKey {
+        X25519PublicKey::from(&EphemeralSecret::new(OsRng))
+    }
+
+    /// Runs `authenticate` on both ends of an in-memory duplex stream
+    /// concurrently, each with its own role and identity, and returns what
+    /// each side resolved the other's identity to.
+    async fn run_pair(
+        client_identity: IdentityKeyPair,
+        client_allowlist: Option<Vec<PublicKey>>,
+        server_identity: IdentityKeyPair,
+        server_allowlist: Option<Vec<PublicKey>>,
+    ) -> (IPCResult<VerifiedIdentity>, IPCResult<VerifiedIdentity>) {
+        let (mut client_stream, mut server_stream) = duplex(4096);
+        let client_ephemeral = ephemeral_public();
+        let server_ephemeral = ephemeral_public();
+
+        let client_fut = authenticate(
+            &mut client_stream,
+            &client_ephemeral,
+            &server_ephemeral,
+            Direction::Initiator,
+            &client_identity,
+            client_allowlist.as_deref(),
+        );
+        let server_fut = authenticate(
+            &mut server_stream,
+            &server_ephemeral,
+            &client_ephemeral,
+            Direction::Responder,
+            &server_identity,
+            server_allowlist.as_deref(),
+        );
+
+        tokio::join!(client_fut, server_fut)
+    }
+
+    #[tokio::test]
+    async fn authenticate_succeeds_for_honest_peers() {
+        let client_identity = identity();
+        let server_identity = identity();
+        let client_public = client_identity.public_key();
+        let server_public = server_identity.public_key();
+
+        let (client_result, server_result) =
+            run_pair(client_identity, None, server_identity, None).await;
+
+        assert_eq!(client_result.unwrap().public_key(), server_public);
+        assert_eq!(server_result.unwrap().public_key(), client_public);
+    }
+
+    #[tokio::test]
+    async fn authenticate_rejects_mismatched_role_tag() {
+        // Both sides claim to be the Initiator, so each signs a transcript
+        // tagged Initiator while the other side verifies against one tagged
+        // Responder - the signature should never check out.
+        let (mut client_stream, mut server_stream) = duplex(4096);
+        let client_ephemeral = ephemeral_public();
+        let server_ephemeral = ephemeral_public();
+        let client_identity = identity();
+        let server_identity = identity();
+
+        let client_fut = authenticate(
+            &mut client_stream,
+            &client_ephemeral,
+            &server_ephemeral,
+            Direction::Initiator,
+            &client_identity,
+            None,
+        );
+        let server_fut = authenticate(
+            &mut server_stream,
+            &server_ephemeral,
+            &client_ephemeral,
+            Direction::Initiator,
+            &server_identity,
+            None,
+        );
+
+        let (client_result, server_result) = tokio::join!(client_fut, server_fut);
+        assert!(client_result.is_err());
+        assert!(server_result.is_err());
+    }
+
+    #[tokio::test]
+    async fn authenticate_rejects_peer_not_on_allowlist() {
+        let client_identity = identity();
+        let server_identity = identity();
+        let unrelated_public = identity().public_key();
+
+        let (client_result, server_result) = run_pair(
+            client_identity,
+            Some(vec![unrelated_public]),
+            server_identity,
+            None,
+        )
+        .await;
+
+        assert!(client_result.is_err());
+        assert!(server_result.is_ok());
+    }
+}
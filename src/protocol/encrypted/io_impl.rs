@@ -2,7 +2,7 @@ use crate::prelude::encrypted::{
     EncryptedPackage, EncryptedReadStream, EncryptedStream, EncryptedWriteStream,
 };
 use crate::prelude::AsyncProtocolStream;
-use crate::protocol::encrypted::crypt_handling::CipherBox;
+use crate::protocol::encrypted::crypt_handling::{CipherBox, DecryptedFrame};
 use bytes::{Buf, BufMut, Bytes};
 use std::cmp::min;
 use std::io;
@@ -58,18 +58,22 @@ impl<T: 'static + AsyncRead + Send + Sync + Unpin> AsyncRead for EncryptedReadSt
 
             if buf.remaining() > 0 {
                 let mut reader = self.inner.take().unwrap();
-                let cipher = self.cipher.take().unwrap();
+                let mut cipher = self.cipher.take().unwrap();
 
                 self.fut = Some(Box::pin(async move {
-                    let package = match EncryptedPackage::from_async_read(&mut reader).await {
-                        Ok(p) => p,
-                        Err(e) => {
-                            return (Err(e), reader, cipher);
+                    // A rekey frame carries no data for the caller: keep
+                    // reading packages until a data frame arrives so rekeying
+                    // stays transparent to the reader above us.
+                    loop {
+                        let package = match EncryptedPackage::from_async_read(&mut reader).await {
+                            Ok(p) => p,
+                            Err(e) => return (Err(e), reader, cipher),
+                        };
+                        match cipher.decrypt(package.into_inner()) {
+                            Ok(DecryptedFrame::Data(bytes)) => return (Ok(bytes), reader, cipher),
+                            Ok(DecryptedFrame::RekeyApplied) => continue,
+                            Err(e) => return (Err(e), reader, cipher),
                         }
-                    };
-                    match cipher.decrypt(package.into_inner()) {
-                        Ok(bytes) => (Ok(bytes), reader, cipher),
-                        Err(e) => (Err(e), reader, cipher),
                     }
                 }));
             }
@@ -203,17 +207,21 @@ impl<T: 'static + AsyncWrite + Unpin + Send + Sync> AsyncWrite for EncryptedWrit
 async fn write_bytes<T: AsyncWrite + Unpin>(
     bytes: Bytes,
     mut writer: T,
-    cipher: CipherBox,
+    mut cipher: CipherBox,
 ) -> (io::Result<()>, T, CipherBox) {
-    let encrypted_bytes = match cipher.encrypt(bytes) {
-        Ok(b) => b,
+    // `encrypt` may return a leading rekey frame in addition to the data
+    // frame; both must reach the peer, in order, before anything else does.
+    let frames = match cipher.encrypt(bytes) {
+        Ok(f) => f,
         Err(e) => {
             return (Err(e), writer, cipher);
         }
     };
-    let package_bytes = EncryptedPackage::new(encrypted_bytes).into_bytes();
-    if let Err(e) = writer.write_all(&package_bytes[..]).await {
-        return (Err(e), writer, cipher);
+    for frame in frames {
+        let package_bytes = EncryptedPackage::new(frame).into_bytes();
+        if let Err(e) = writer.write_all(&package_bytes[..]).await {
+            return (Err(e), writer, cipher);
+        }
     }
 
     (Ok(()), writer, cipher)
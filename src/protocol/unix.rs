@@ -0,0 +1,177 @@
+//! A Unix domain socket transport, implementing the same
+//! `AsyncStreamProtocolListener`/`AsyncProtocolStream`/`AsyncProtocolStreamSplit`
+//! traits as [`tcp`](super::tcp) so it layers with the encrypted/compressed
+//! wrappers and the `IPCBuilder` exactly like TCP does.
+//!
+//! [`AddressType`] covers both a regular filesystem path and, on Linux, an
+//! abstract-namespace name.
+#![cfg(feature = "unix_socket")]
+
+use crate::prelude::IPCResult;
+use crate::protocol::{AsyncProtocolStream, AsyncProtocolStreamSplit, AsyncStreamProtocolListener};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Address for the Unix domain socket transport: either a regular
+/// filesystem path or, on Linux, a name in the abstract namespace (no
+/// filesystem entry, automatically reclaimed once every socket bound to it
+/// is closed).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddressType {
+    /// A regular filesystem path, e.g. `/run/bromine.sock`.
+    Path(PathBuf),
+    /// A name in the Linux abstract socket namespace.
+    #[cfg(target_os = "linux")]
+    Abstract(String),
+}
+
+impl From<PathBuf> for AddressType {
+    fn from(path: PathBuf) -> Self {
+        AddressType::Path(path)
+    }
+}
+
+impl From<&Path> for AddressType {
+    fn from(path: &Path) -> Self {
+        AddressType::Path(path.to_path_buf())
+    }
+}
+
+#[async_trait]
+impl AsyncStreamProtocolListener for UnixListener {
+    type AddressType = AddressType;
+    type RemoteAddressType = AddressType;
+    type Stream = UnixStream;
+    type ListenerOptions = ();
+
+    async fn protocol_bind(
+        address: Self::AddressType,
+        _: Self::ListenerOptions,
+    ) -> IPCResult<Self> {
+        match address {
+            AddressType::Path(path) => Ok(UnixListener::bind(path)?),
+            #[cfg(target_os = "linux")]
+            AddressType::Abstract(name) => abstract_namespace::bind_listener(&name),
+        }
+    }
+
+    async fn protocol_accept(&self) -> IPCResult<(Self::Stream, Self::RemoteAddressType)> {
+        let (stream, addr) = self.accept().await?;
+        // Unix clients usually connect anonymously, so there is often no
+        // peer path to report even on the filesystem-path variant.
+        let remote = addr
+            .as_pathname()
+            .map(|path| AddressType::Path(path.to_path_buf()))
+            .unwrap_or_else(|| AddressType::Path(PathBuf::new()));
+
+        Ok((stream, remote))
+    }
+}
+
+impl AsyncProtocolStreamSplit for UnixStream {
+    type OwnedSplitReadHalf = OwnedReadHalf;
+    type OwnedSplitWriteHalf = OwnedWriteHalf;
+
+    fn protocol_into_split(self) -> (Self::OwnedSplitReadHalf, Self::OwnedSplitWriteHalf) {
+        self.into_split()
+    }
+}
+
+#[async_trait]
+impl AsyncProtocolStream for UnixStream {
+    type AddressType = AddressType;
+    type StreamOptions = ();
+
+    async fn protocol_connect(
+        address: Self::AddressType,
+        _: Self::StreamOptions,
+    ) -> IPCResult<Self> {
+        match address {
+            AddressType::Path(path) => Ok(UnixStream::connect(path).await?),
+            #[cfg(target_os = "linux")]
+            AddressType::Abstract(name) => abstract_namespace::connect_stream(&name).await,
+        }
+    }
+}
+
+/// Abstract-namespace addresses are a Linux-only extension: the address's
+/// path starts with a NUL byte (which can never appear in a real
+/// filesystem path), so the kernel never creates a directory entry for it.
+/// Neither `std` nor tokio expose a stable way to bind/connect to one, so
+/// this builds the `sockaddr_un` by hand and hands the resulting fd to
+/// tokio via `from_std`.
+#[cfg(target_os = "linux")]
+mod abstract_namespace {
+    use crate::prelude::IPCResult;
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::{FromRawFd, RawFd};
+    use tokio::net::{UnixListener, UnixStream};
+
+    pub fn bind_listener(name: &str) -> IPCResult<UnixListener> {
+        let fd = unsafe { create_abstract_socket(name, true)? };
+        let std_listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+        std_listener.set_nonblocking(true)?;
+
+        Ok(UnixListener::from_std(std_listener)?)
+    }
+
+    pub async fn connect_stream(name: &str) -> IPCResult<UnixStream> {
+        let fd = unsafe { create_abstract_socket(name, false)? };
+        let std_stream = unsafe { std::os::unix::net::UnixStream::from_raw_fd(fd) };
+        std_stream.set_nonblocking(true)?;
+
+        Ok(UnixStream::from_std(std_stream)?)
+    }
+
+    /// Builds a `sockaddr_un` whose path is a NUL byte followed by `name`,
+    /// then binds+listens or connects a fresh socket to it.
+    unsafe fn create_abstract_socket(name: &str, listen: bool) -> io::Result<RawFd> {
+        let fd = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut addr: libc::sockaddr_un = mem::zeroed();
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+        let name_bytes = name.as_bytes();
+        if name_bytes.len() + 1 > addr.sun_path.len() {
+            libc::close(fd);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "abstract socket name too long",
+            ));
+        }
+        let path_bytes = std::slice::from_raw_parts_mut(
+            addr.sun_path.as_mut_ptr() as *mut u8,
+            addr.sun_path.len(),
+        );
+        path_bytes[0] = 0;
+        path_bytes[1..=name_bytes.len()].copy_from_slice(name_bytes);
+        let addr_len =
+            (mem::size_of::<libc::sa_family_t>() + 1 + name_bytes.len()) as libc::socklen_t;
+
+        let result = if listen {
+            let bind_result =
+                libc::bind(fd, &addr as *const _ as *const libc::sockaddr, addr_len);
+            if bind_result == 0 {
+                libc::listen(fd, 128)
+            } else {
+                bind_result
+            }
+        } else {
+            libc::connect(fd, &addr as *const _ as *const libc::sockaddr, addr_len)
+        };
+
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(err);
+        }
+
+        Ok(fd)
+    }
+}